@@ -18,9 +18,9 @@
 //! exposes extension functions and shortcuts to aid with creating and managing graphics resources.
 //! See the `FactoryExt` trait for more information.
 
-use gfx_core::{format, handle, tex, state};
-use gfx_core::{Primitive, Resources, ShaderSet};
-use gfx_core::factory::{Bind, BufferRole, Factory};
+use gfx_core::{format, handle, mapping, tex, state};
+use gfx_core::{InstanceCount, Primitive, Resources, ShaderSet, VertexCount};
+use gfx_core::factory::{Bind, BufferRole, Factory, ResourceViewError};
 use gfx_core::pso::{CreationError, Descriptor};
 use slice::{Slice, IndexBuffer, IntoIndexBuffer};
 use pso;
@@ -35,8 +35,73 @@ pub enum PipelineStateError {
     DescriptorInit(pso::InitError),
     /// Device failed to create the handle give the descriptor.
     DeviceCreate(CreationError),
+    /// `PipelineStateBuilder::build` was called without first setting `.shaders(..)`.
+    NoShaders,
 }
 
+/// Error creating a texture and shader resource view from initial mip/slice data.
+#[derive(Clone, PartialEq, Debug)]
+pub enum CombinedError {
+    /// The number of slices/mip levels in the supplied data doesn't match `Kind`.
+    /// Carries `(got, expected)`.
+    Data(usize, usize),
+    /// Failed to create the raw texture.
+    Texture(tex::CreationError),
+    /// Failed to create the resource view.
+    View(ResourceViewError),
+}
+
+/// A builder for `PipelineState`, allowing the primitive, rasterizer and shaders to be
+/// overridden individually instead of specified all at once. Created by
+/// `FactoryExt::pipeline_builder`.
+pub struct PipelineStateBuilder<'a, R: Resources, F: Factory<R> + 'a, I: pso::PipelineInit> {
+    factory: &'a mut F,
+    shaders: Option<ShaderSet<R>>,
+    primitive: Primitive,
+    rasterizer: state::Rasterizer,
+    init: I,
+}
+
+impl<'a, R: Resources, F: Factory<R> + 'a, I: pso::PipelineInit> PipelineStateBuilder<'a, R, F, I> {
+    /// Set the shaders the pipeline will be linked from.
+    pub fn shaders(mut self, shaders: ShaderSet<R>) -> Self {
+        self.shaders = Some(shaders);
+        self
+    }
+
+    /// Set the primitive topology.
+    pub fn primitive(mut self, primitive: Primitive) -> Self {
+        self.primitive = primitive;
+        self
+    }
+
+    /// Set the rasterizer state.
+    pub fn rasterizer(mut self, rasterizer: state::Rasterizer) -> Self {
+        self.rasterizer = rasterizer;
+        self
+    }
+
+    /// Shorthand for `.primitive(Primitive::TriangleList)`.
+    pub fn triangle_list(self) -> Self {
+        self.primitive(Primitive::TriangleList)
+    }
+
+    /// Shorthand for `.rasterizer(state::Rasterizer::new_fill())`.
+    pub fn with_fill(self) -> Self {
+        let rasterizer = state::Rasterizer::new_fill();
+        self.rasterizer(rasterizer)
+    }
+
+    /// Build the `PipelineState` from the accumulated shaders, primitive, rasterizer and
+    /// `Init` structure.
+    pub fn build(self) -> Result<pso::PipelineState<R, I::Meta>, PipelineStateError> {
+        let shaders = match self.shaders {
+            Some(s) => s,
+            None => return Err(PipelineStateError::NoShaders),
+        };
+        self.factory.create_pipeline_state(&shaders, self.primitive, self.rasterizer, self.init)
+    }
+}
 
 /// This trait is responsible for creating and managing graphics resources, much like the `Factory`
 /// trait in the `gfx` crate. Every `Factory` automatically implements `FactoryExt`. 
@@ -75,12 +140,52 @@ pub trait FactoryExt<R: Resources>: Factory<R> {
         })
     }
 
+    /// Shorthand for creating a new vertex buffer from the supplied vertices, together with an
+    /// instanced `Slice` from the supplied indices, drawing `count` instances starting at
+    /// `base`.
+    fn create_vertex_buffer_with_slice_instanced<B, V>(&mut self, vertices: &[V], indices: B,
+                                                        count: InstanceCount, base: VertexCount)
+                                                        -> (handle::Buffer<R, V>, Slice<R>)
+                                                        where V: Copy + pso::buffer::Structure<format::Format>,
+                                                              B: IntoIndexBuffer<R>
+    {
+        let (vertex_buffer, mut slice) = self.create_vertex_buffer_with_slice(vertices, indices);
+        slice.instances = Some((count, base));
+        (vertex_buffer, slice)
+    }
+
     /// Create a constant buffer for `num` identical elements of type `T`.
     fn create_constant_buffer<T>(&mut self, num: usize) -> handle::Buffer<R, T> {
         self.create_buffer_dynamic(num, BufferRole::Uniform, Bind::empty())
             .unwrap()
     }
 
+    /// Update the contents of `buf` in place by acquiring a write mapping, handing the caller a
+    /// mutable slice to fill in, and releasing the mapping again.
+    fn update_buffer_mapped<T, F>(&mut self, buf: &handle::Buffer<R, T>, f: F)
+                            -> Result<(), mapping::Error> where
+                            T: Copy, F: FnOnce(&mut [T])
+    {
+        let mut writer = match self.map_writable(buf) {
+            Ok(w) => w,
+            Err(e) => return Err(e),
+        };
+        f(&mut writer);
+        Ok(())
+    }
+
+    /// Read back the contents of `buf` by acquiring a read mapping and copying it out.
+    fn read_buffer_mapped<T>(&mut self, buf: &handle::Buffer<R, T>)
+                         -> Result<Vec<T>, mapping::Error> where
+                         T: Copy
+    {
+        let reader = match self.map_readable(buf) {
+            Ok(r) => r,
+            Err(e) => return Err(e),
+        };
+        Ok(reader.iter().cloned().collect())
+    }
+
     /// Creates a `ShaderSet` from the supplied vertex and pixel shader source code.
     fn create_shader_set(&mut self, vs_code: &[u8], ps_code: &[u8])
                          -> Result<ShaderSet<R>, ProgramError> {
@@ -104,6 +209,68 @@ pub trait FactoryExt<R: Resources>: Factory<R> {
             .map_err(|e| ProgramError::Link(e))
     }
 
+    /// Creates a `ShaderSet` with a geometry stage from the supplied vertex, geometry and pixel
+    /// shader source code.
+    fn create_shader_set_geometry(&mut self, vs_code: &[u8], gs_code: &[u8], ps_code: &[u8])
+                                  -> Result<ShaderSet<R>, ProgramError> {
+        let vs = match self.create_shader_vertex(vs_code) {
+            Ok(s) => s,
+            Err(e) => return Err(ProgramError::Vertex(e)),
+        };
+        let gs = match self.create_shader_geometry(gs_code) {
+            Ok(s) => s,
+            Err(e) => return Err(ProgramError::Geometry(e)),
+        };
+        let ps = match self.create_shader_pixel(ps_code) {
+            Ok(s) => s,
+            Err(e) => return Err(ProgramError::Pixel(e)),
+        };
+        Ok(ShaderSet::Geometry(vs, gs, ps))
+    }
+
+    /// Creates a `ShaderSet` with hull and domain (tessellation) stages from the supplied
+    /// vertex, hull, domain and pixel shader source code.
+    fn create_shader_set_tessellation(&mut self, vs_code: &[u8], hs_code: &[u8], ds_code: &[u8],
+                                      ps_code: &[u8])
+                                      -> Result<ShaderSet<R>, ProgramError> {
+        let vs = match self.create_shader_vertex(vs_code) {
+            Ok(s) => s,
+            Err(e) => return Err(ProgramError::Vertex(e)),
+        };
+        let hs = match self.create_shader_hull(hs_code) {
+            Ok(s) => s,
+            Err(e) => return Err(ProgramError::Hull(e)),
+        };
+        let ds = match self.create_shader_domain(ds_code) {
+            Ok(s) => s,
+            Err(e) => return Err(ProgramError::Domain(e)),
+        };
+        let ps = match self.create_shader_pixel(ps_code) {
+            Ok(s) => s,
+            Err(e) => return Err(ProgramError::Pixel(e)),
+        };
+        Ok(ShaderSet::Tessellated(vs, hs, ds, ps))
+    }
+
+    /// Creates a shader `Program` with a geometry stage from the supplied vertex, geometry and
+    /// pixel shader source code.
+    fn link_program_geometry(&mut self, vs_code: &[u8], gs_code: &[u8], ps_code: &[u8])
+                             -> Result<handle::Program<R>, ProgramError> {
+        let set = try!(self.create_shader_set_geometry(vs_code, gs_code, ps_code));
+        self.create_program(&set)
+            .map_err(|e| ProgramError::Link(e))
+    }
+
+    /// Creates a shader `Program` with hull and domain (tessellation) stages from the supplied
+    /// vertex, hull, domain and pixel shader source code.
+    fn link_program_tessellation(&mut self, vs_code: &[u8], hs_code: &[u8], ds_code: &[u8],
+                                 ps_code: &[u8])
+                                 -> Result<handle::Program<R>, ProgramError> {
+        let set = try!(self.create_shader_set_tessellation(vs_code, hs_code, ds_code, ps_code));
+        self.create_program(&set)
+            .map_err(|e| ProgramError::Link(e))
+    }
+
     /// Similar to `create_pipeline_from_program(..)`, but takes a `ShaderSet` as opposed to a
     /// shader `Program`.  
     fn create_pipeline_state<I: pso::PipelineInit>(&mut self, shaders: &ShaderSet<R>,
@@ -148,6 +315,21 @@ pub trait FactoryExt<R: Resources>: Factory<R> {
         }
     }
 
+    /// Start building a `PipelineState` by chaining calls to override its shaders, primitive
+    /// and rasterizer one at a time, rather than specifying all of them up front. Defaults to
+    /// `Primitive::TriangleList` and `state::Rasterizer::new_fill()`.
+    fn pipeline_builder<I: pso::PipelineInit>(&mut self, init: I) -> PipelineStateBuilder<R, Self, I>
+        where Self: Sized
+    {
+        PipelineStateBuilder {
+            factory: self,
+            shaders: None,
+            primitive: Primitive::TriangleList,
+            rasterizer: state::Rasterizer::new_fill(),
+            init: init,
+        }
+    }
+
     /// Create a linear sampler with clamping to border.
     fn create_sampler_linear(&mut self) -> handle::Sampler<R> {
         self.create_sampler(tex::SamplerInfo::new(
@@ -155,6 +337,34 @@ pub trait FactoryExt<R: Resources>: Factory<R> {
             tex::WrapMode::Clamp,
         ))
     }
+
+    /// Create an immutable texture of the given `Kind`, initialized with `data` laid out as
+    /// Slice0.Mip0, Slice0.Mip1, ..., Slice1.Mip0, ... and return both the texture handle and a
+    /// sampleable shader resource view for it.
+    fn create_texture_immutable<T: format::TextureFormat>(&mut self, kind: tex::Kind,
+                                data: &[&[<T::Surface as format::SurfaceTyped>::DataType]])
+                                -> Result<(handle::Texture<R, T::Surface>,
+                                           handle::ShaderResourceView<R, T::View>), CombinedError>
+        where <T::Surface as format::SurfaceTyped>::DataType: Copy
+    {
+        let num_slices = kind.get_num_slices().unwrap_or(1) as usize;
+        let num_levels = kind.get_num_levels() as usize;
+        let expected = num_slices * num_levels;
+        if data.len() != expected {
+            return Err(CombinedError::Data(data.len(), expected));
+        }
+
+        let tex = match self.create_texture::<T::Surface>(kind, num_levels as tex::Level,
+                                                           Bind::SHADER_RESOURCE, Some(data)) {
+            Ok(t) => t,
+            Err(e) => return Err(CombinedError::Texture(e)),
+        };
+        match self.view_texture_as_shader_resource::<T>(&tex, (0, num_levels as u8 - 1),
+                                                         format::Swizzle::new()) {
+            Ok(view) => Ok((tex, view)),
+            Err(e) => Err(CombinedError::View(e)),
+        }
+    }
 }
 
 impl<R: Resources, F: Factory<R>> FactoryExt<R> for F {}